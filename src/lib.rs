@@ -3,37 +3,215 @@ use std::{
     fmt::{Display, Formatter},
 };
 
-type Callback = Box<dyn Fn(&Engine, usize)>;
+/// Whether a listener's variable lost support for some value(s) or regained it,
+/// e.g. by retracting the constraint that had suppressed them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomainChange {
+    Removed,
+    Restored,
+}
+
+/// `(engine, var, constraint, change)` — `constraint` is the id responsible for
+/// the change (or [`ASSIGNMENT_MARKER`] for an [`Engine::assign`] restriction).
+type Callback = Box<dyn Fn(&Engine, usize, usize, DomainChange)>;
+
+/// A [`Relation::Predicate`]'s n-ary test, as supplied to [`Engine::new_predicate`].
+type Predicate = Box<dyn Fn(&[i32]) -> bool>;
 
 #[derive(Debug, PartialEq)]
 enum PropagationError {
     DomainWipeout(usize), // The ID of the variable that became empty
+    /// The `max_revisions` budget ran out mid-pass; carries the constraint ids
+    /// still queued (the one being revised plus everything behind it). Also
+    /// raised when a predicate constraint's domain product exceeds
+    /// [`MAX_PREDICATE_DOMAIN_PRODUCT`]; then it carries only that constraint's id.
+    Overflow(Vec<usize>),
+}
+
+/// Why a call that propagates constraints failed.
+#[derive(Debug, PartialEq)]
+pub enum EngineError {
+    /// Propagation wiped out a variable's domain. `.1` is the minimal set of
+    /// constraint ids responsible, per [`Engine::get_conflict_explanation`].
+    /// For a conflict raised by [`Engine::assign`], `.0` is [`ASSIGNMENT_MARKER`]
+    /// and `.1` may also contain it, standing for the assignment itself.
+    Conflict(usize, Vec<usize>),
+    /// The [`Engine::set_max_revisions`] budget ran out before propagation
+    /// stabilized. Carries the constraint ids still pending when it was hit.
+    /// Also raised when an [`Engine::new_predicate`] constraint's combined
+    /// active-domain size exceeds its cap; then it carries only that constraint's id.
+    Overflow(Vec<usize>),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ConstraintKind {
     Equality,
     Inequality,
+    /// `var1 <= var2`
+    LessEqual,
+    /// `var1 < var2`
+    LessThan,
+    /// `var1 == var2 + k`
+    Offset(i32),
+}
+
+/// What a [`Constraint`] enforces: one of the built-in binary relations, or an
+/// arbitrary user-supplied n-ary predicate enforced via generalized arc
+/// consistency (GAC).
+enum Relation {
+    Binary(ConstraintKind),
+    /// Satisfied iff `pred(values)` returns `true`, where `values` lines up
+    /// 1:1 with the owning [`Constraint`]'s `vars`, in order.
+    Predicate(Predicate),
+}
+
+/// A constraint over one or more variables, stored under its id in `Engine::constraints`.
+struct Constraint {
+    vars: Vec<usize>,
+    relation: Relation,
+}
+
+/// Why a value lost support: the constraint that suppressed it, plus the specific
+/// (var, value) pairs in the opposite domain whose earlier deletion removed its
+/// last support. Following `causes` backward builds an implication DAG that
+/// explains a domain wipeout rather than just naming its immediate suppressors.
+#[derive(Clone)]
+struct Reason {
+    constraint: usize,
+    causes: Vec<(usize, i32)>,
 }
 
 struct ValueState {
     value: i32,
     suppressed_by: Option<usize>,
+    reason: Option<Reason>,
 }
 
+/// A single undoable mutation recorded while the engine runs, so that a search
+/// driver can cheaply rewind to an earlier point without re-propagating from scratch.
+enum JournalEntry {
+    /// `values[var][idx]` held `old_suppressed_by`/`old_reason` before this change.
+    ValueChange { var: usize, idx: usize, old_suppressed_by: Option<usize>, old_reason: Option<Reason> },
+    /// A constraint was inserted under this id; undo by removing it.
+    ConstraintAdded(usize),
+    /// A constraint was removed from under this id; undo by reinserting it.
+    ConstraintRemoved(usize, Constraint),
+}
+
+/// An opaque mark on the journal produced by [`Engine::push`]. Pass it to
+/// [`Engine::pop_to`] to undo every mutation recorded since the mark was taken.
+pub struct Checkpoint(usize);
+
+/// Sentinel `suppressed_by` id used by [`Engine::assign`] for values it suppresses
+/// itself, distinct from any real constraint id (which are dense, small `usize`s).
+const ASSIGNMENT_MARKER: usize = usize::MAX;
+
+/// Ceiling on the Cartesian product of an n-ary [`Engine::new_predicate`]
+/// constraint's active domains. Beyond this, exhaustively searching for
+/// support is intractable, so the constraint reports a
+/// [`PropagationError::Overflow`] instead of hanging.
+const MAX_PREDICATE_DOMAIN_PRODUCT: usize = 1_000_000;
+
 pub struct Engine {
     values: Vec<Vec<ValueState>>,
-    constraints: HashMap<usize, (usize, usize, ConstraintKind)>,
+    constraints: HashMap<usize, Constraint>,
     listeners: HashMap<usize, Vec<Callback>>,
+    journal: Vec<JournalEntry>,
+    /// Minimal conflict sets (nogoods) discovered by past wipeouts, so that
+    /// re-adding a superset of a known-bad combination fails immediately.
+    /// Entries are keyed by constraint id, so they (and conflict explanations
+    /// built from `Reason.constraint`) are only trustworthy as long as ids
+    /// are never reused — see `next_id`.
+    nogoods: Vec<HashSet<usize>>,
+    /// Caps how many `revise` calls a single propagation pass may perform, see
+    /// [`Engine::set_max_revisions`]. Defaults to `usize::MAX` (unbounded).
+    max_revisions: usize,
+    /// Next id to hand out in [`Engine::new_constraint_with`]. Monotonically
+    /// increasing and never reused, so retracting a constraint out of
+    /// insertion order can never free an id that collides with one still
+    /// live in `constraints` (which `constraints.len()` would).
+    next_id: usize,
 }
 impl Engine {
     pub fn new() -> Self {
-        Self { values: Vec::new(), constraints: HashMap::new(), listeners: HashMap::new() }
+        Self {
+            values: Vec::new(),
+            constraints: HashMap::new(),
+            listeners: HashMap::new(),
+            journal: Vec::new(),
+            nogoods: Vec::new(),
+            max_revisions: usize::MAX,
+            next_id: 0,
+        }
+    }
+
+    /// Caps how many `revise` calls a single propagation pass may perform before
+    /// giving up with [`EngineError::Overflow`], guarding against runaway
+    /// fixpoint iteration on cyclic or arithmetic (e.g. [`Engine::new_offset`])
+    /// constraints that could otherwise oscillate indefinitely. Defaults to
+    /// `usize::MAX`, which is effectively unbounded.
+    pub fn set_max_revisions(&mut self, max_revisions: usize) {
+        self.max_revisions = max_revisions;
+    }
+
+    /// Records the current point in the journal. Pair with [`Engine::pop_to`] to
+    /// cheaply undo everything that happens in between, e.g. a search driver
+    /// trying a branch via [`Engine::assign`] and backtracking on failure.
+    pub fn push(&mut self) -> Checkpoint {
+        Checkpoint(self.journal.len())
+    }
+
+    /// Replays the journal in reverse back to `checkpoint`, restoring every
+    /// `suppressed_by` field and constraint that changed since it was taken.
+    /// Afterwards the engine's domains and constraints are exactly as they were
+    /// at `push` time, regardless of how much propagation happened in between.
+    pub fn pop_to(&mut self, checkpoint: Checkpoint) {
+        while self.journal.len() > checkpoint.0 {
+            match self.journal.pop().unwrap() {
+                JournalEntry::ValueChange { var, idx, old_suppressed_by, old_reason } => {
+                    self.values[var][idx].suppressed_by = old_suppressed_by;
+                    self.values[var][idx].reason = old_reason;
+                }
+                JournalEntry::ConstraintAdded(id) => {
+                    self.constraints.remove(&id);
+                }
+                JournalEntry::ConstraintRemoved(id, constraint) => {
+                    self.constraints.insert(id, constraint);
+                }
+            }
+        }
+    }
+
+    /// Installs a temporary unary restriction pinning `var` to `value`, propagates
+    /// it, and journals every value it suppresses so a search driver can try this
+    /// assignment and cheaply undo it with [`Engine::pop_to`] on failure.
+    pub fn assign(&mut self, var: usize, value: i32) -> Result<(), EngineError> {
+        let mut suppressed_any = false;
+        for (idx, state) in self.values[var].iter_mut().enumerate() {
+            if state.value != value && state.suppressed_by.is_none() {
+                self.journal.push(JournalEntry::ValueChange { var, idx, old_suppressed_by: None, old_reason: None });
+                state.suppressed_by = Some(ASSIGNMENT_MARKER);
+                state.reason = Some(Reason { constraint: ASSIGNMENT_MARKER, causes: Vec::new() });
+                suppressed_any = true;
+            }
+        }
+        if suppressed_any {
+            self.notify(var, ASSIGNMENT_MARKER, DomainChange::Removed);
+        }
+
+        if !self.values[var].iter().any(|s| s.suppressed_by.is_none()) {
+            return Err(EngineError::Conflict(ASSIGNMENT_MARKER, self.get_conflict_explanation(var)));
+        }
+
+        self.propagate_touching(&[var]).map_err(|e| match e {
+            PropagationError::DomainWipeout(var_id) => EngineError::Conflict(ASSIGNMENT_MARKER, self.get_conflict_explanation(var_id)),
+            PropagationError::Overflow(pending) => EngineError::Overflow(pending),
+        })
     }
 
     pub fn add_var(&mut self, values: Vec<i32>) -> usize {
         let id = self.values.len();
-        self.values.push(values.into_iter().map(|v| ValueState { value: v, suppressed_by: None }).collect());
+        self.values.push(values.into_iter().map(|v| ValueState { value: v, suppressed_by: None, reason: None }).collect());
         id
     }
 
@@ -41,44 +219,120 @@ impl Engine {
         self.values[var].iter().filter(|s| s.suppressed_by.is_none()).map(|s| s.value).collect()
     }
 
-    pub fn new_eq(&mut self, var1: usize, var2: usize) -> Result<usize, (usize, Vec<usize>)> {
-        let id = self.constraints.len();
-        self.constraints.insert(id, (var1, var2, ConstraintKind::Equality));
-        self.propagate(id).map_err(|e| match e {
-            PropagationError::DomainWipeout(var_id) => (id, self.get_conflict_explanation(var_id)),
-        })?;
-        Ok(id)
+    pub fn new_eq(&mut self, var1: usize, var2: usize) -> Result<usize, EngineError> {
+        self.new_constraint(var1, var2, ConstraintKind::Equality)
+    }
+
+    pub fn new_neq(&mut self, var1: usize, var2: usize) -> Result<usize, EngineError> {
+        self.new_constraint(var1, var2, ConstraintKind::Inequality)
+    }
+
+    /// `var1 <= var2`
+    pub fn new_le(&mut self, var1: usize, var2: usize) -> Result<usize, EngineError> {
+        self.new_constraint(var1, var2, ConstraintKind::LessEqual)
+    }
+
+    /// `var1 < var2`
+    pub fn new_lt(&mut self, var1: usize, var2: usize) -> Result<usize, EngineError> {
+        self.new_constraint(var1, var2, ConstraintKind::LessThan)
+    }
+
+    /// `var1 == var2 + k`
+    pub fn new_offset(&mut self, var1: usize, var2: usize, k: i32) -> Result<usize, EngineError> {
+        self.new_constraint(var1, var2, ConstraintKind::Offset(k))
+    }
+
+    /// Registers an arbitrary constraint over `vars`, enforced via generalized
+    /// arc consistency (GAC) instead of one of the built-in binary relations:
+    /// a value of any variable in `vars` keeps support iff some assignment of
+    /// the *other* variables, drawn from their active domains, makes `pred`
+    /// return `true`. `pred` receives one value per entry of `vars`, in the
+    /// same order. Fails with [`EngineError::Overflow`] instead of exhaustively
+    /// searching once the constraint's combined active-domain size exceeds a
+    /// tractable cap.
+    pub fn new_predicate(&mut self, vars: Vec<usize>, pred: Predicate) -> Result<usize, EngineError> {
+        self.new_constraint_with(vars, Relation::Predicate(pred))
+    }
+
+    fn new_constraint(&mut self, var1: usize, var2: usize, kind: ConstraintKind) -> Result<usize, EngineError> {
+        self.new_constraint_with(vec![var1, var2], Relation::Binary(kind))
     }
 
-    pub fn new_neq(&mut self, var1: usize, var2: usize) -> Result<usize, (usize, Vec<usize>)> {
-        let id = self.constraints.len();
-        self.constraints.insert(id, (var1, var2, ConstraintKind::Inequality));
+    fn new_constraint_with(&mut self, vars: Vec<usize>, relation: Relation) -> Result<usize, EngineError> {
+        // Never derived from `constraints.len()`: retracting any constraint
+        // that isn't the most recently added one shrinks the map without
+        // freeing the *highest* id, so a length-based id would collide with
+        // (and silently clobber, via `HashMap::insert`) a still-active one.
+        let id = self.next_id;
+        self.next_id += 1;
+
+        // Check before registering: if we inserted first, a cached-nogood hit
+        // would leave `id` permanently marked "active" in `self.constraints`
+        // without ever having been propagated, corrupting every domain and
+        // retraction invariant that assumes an active constraint was enforced.
+        if let Some(nogood) = self.matching_nogood() {
+            return Err(EngineError::Conflict(id, nogood));
+        }
+
+        self.constraints.insert(id, Constraint { vars, relation });
+        self.journal.push(JournalEntry::ConstraintAdded(id));
+
         self.propagate(id).map_err(|e| match e {
-            PropagationError::DomainWipeout(var_id) => (id, self.get_conflict_explanation(var_id)),
+            PropagationError::DomainWipeout(var_id) => {
+                let nogood = self.get_conflict_explanation(var_id);
+                self.nogoods.push(nogood.iter().copied().collect());
+                EngineError::Conflict(id, nogood)
+            }
+            PropagationError::Overflow(pending) => EngineError::Overflow(pending),
         })?;
         Ok(id)
     }
 
-    pub fn retract_constraint(&mut self, id: usize) {
-        if let Some((var1, var2, _)) = self.constraints.remove(&id) {
+    /// Returns a previously recorded nogood if the currently active constraints
+    /// are a superset of it, so a doomed combination fails immediately instead
+    /// of re-propagating to rediscover the same conflict.
+    fn matching_nogood(&self) -> Option<Vec<usize>> {
+        let active: HashSet<usize> = self.constraints.keys().copied().collect();
+        self.nogoods.iter().find(|ng| ng.is_subset(&active)).map(|ng| ng.iter().copied().collect())
+    }
+
+    pub fn retract_constraint(&mut self, id: usize) -> Result<(), EngineError> {
+        if let Some(constraint) = self.constraints.remove(&id) {
+            let vars = constraint.vars.clone();
+            self.journal.push(JournalEntry::ConstraintRemoved(id, constraint));
+
             // 1. Free only values that were killed *by this exact constraint*
-            for &var in &[var1, var2] {
+            for &var in &vars {
                 if let Some(domain) = self.values.get_mut(var) {
-                    for state in domain {
+                    let mut revived = false;
+                    for (idx, state) in domain.iter_mut().enumerate() {
                         if state.suppressed_by == Some(id) {
+                            self.journal.push(JournalEntry::ValueChange { var, idx, old_suppressed_by: Some(id), old_reason: state.reason.clone() });
                             state.suppressed_by = None;
+                            state.reason = None;
+                            revived = true;
                         }
                     }
+                    if revived {
+                        self.notify(var, id, DomainChange::Restored);
+                    }
                 }
             }
 
             // 2. Re-propagate only the affected subgraph (true incremental)
-            self.propagate_touching(&[var1, var2]).unwrap_or_else(|e| match e {
+            self.propagate_touching(&vars).map_err(|e| match e {
                 PropagationError::DomainWipeout(var_id) => {
                     panic!("Unexpected domain wipeout during re-propagation after retracting constraint {}: variable {}", id, var_id)
                 }
-            });
+                PropagationError::Overflow(pending) => EngineError::Overflow(pending),
+            })?;
+
+            // 3. Ids are never reused (see `next_id`), so `id` can never be
+            // active again and any nogood naming it can never match; drop
+            // such nogoods rather than let them outlive the constraint.
+            self.nogoods.retain(|ng| !ng.contains(&id));
         }
+        Ok(())
     }
 
     fn propagate(&mut self, start_id: usize) -> Result<(), PropagationError> {
@@ -88,8 +342,8 @@ impl Engine {
     fn propagate_touching(&mut self, vars: &[usize]) -> Result<(), PropagationError> {
         let mut initial = Vec::new();
         for &v in vars {
-            for (&id, (v1, v2, _)) in &self.constraints {
-                if *v1 == v || *v2 == v {
+            for (&id, constraint) in &self.constraints {
+                if constraint.vars.contains(&v) {
                     initial.push(id);
                 }
             }
@@ -100,20 +354,74 @@ impl Engine {
     fn propagate_from_queue(&mut self, initial: Vec<usize>) -> Result<(), PropagationError> {
         let mut prop_q: VecDeque<usize> = initial.into();
         let mut in_queue: HashSet<usize> = prop_q.iter().cloned().collect();
+        // Listeners fire at most once per variable for this whole pass, even if
+        // several constraints narrow the same variable before it stabilizes.
+        let mut notified: HashSet<usize> = HashSet::new();
+        // Bounds this pass's work so cyclic/arithmetic constraints that oscillate
+        // (e.g. offsets around a loop) can't spin forever; see `max_revisions`.
+        let mut revisions: usize = 0;
 
         while let Some(c) = prop_q.pop_front() {
             in_queue.remove(&c);
 
-            let (var1, var2, kind) = *self.constraints.get(&c).unwrap();
+            // Binary constraints keep their specialized bounds-style `revise`;
+            // n-ary predicate constraints go through the GAC search instead.
+            // Either way we come away with `vars` (the constraint's full
+            // variable list, for re-enqueueing) and `changes` (which of those
+            // variables actually had their domain narrowed or widened).
+            let (vars, changes) = match &self.constraints.get(&c).unwrap().relation {
+                Relation::Binary(kind) => {
+                    let kind = *kind;
+                    let constraint_vars = self.constraints.get(&c).unwrap().vars.clone();
+                    let (var1, var2) = (constraint_vars[0], constraint_vars[1]);
+
+                    revisions += 1;
+                    self.check_revision_budget(revisions, c, &prop_q)?;
+                    let change1 = self.revise(var1, var2, kind, c, true)?;
 
-            let changed1 = self.revise(var1, var2, kind, c)?;
-            let changed2 = self.revise(var2, var1, kind, c)?;
+                    revisions += 1;
+                    self.check_revision_budget(revisions, c, &prop_q)?;
+                    let change2 = self.revise(var2, var1, kind, c, false)?;
+
+                    let mut changes = Vec::new();
+                    if let Some(change) = change1 {
+                        changes.push((var1, change));
+                    }
+                    if let Some(change) = change2 {
+                        changes.push((var2, change));
+                    }
+                    (constraint_vars, changes)
+                }
+                Relation::Predicate(_) => {
+                    // Take the constraint out so `self` is free to mutate
+                    // while we hold its predicate; put it back afterwards.
+                    let constraint = self.constraints.remove(&c).unwrap();
+                    let constraint_vars = constraint.vars.clone();
 
-            if changed1 || changed2 {
+                    revisions += constraint_vars.len();
+                    if let Err(e) = self.check_revision_budget(revisions, c, &prop_q) {
+                        self.constraints.insert(c, constraint);
+                        return Err(e);
+                    }
+
+                    let Relation::Predicate(pred) = &constraint.relation else { unreachable!() };
+                    let result = self.revise_predicate(&constraint_vars, pred.as_ref(), c);
+                    self.constraints.insert(c, constraint);
+                    (constraint_vars, result?)
+                }
+            };
+
+            for &(var, change) in &changes {
+                if notified.insert(var) {
+                    self.notify(var, c, change);
+                }
+            }
+
+            if !changes.is_empty() {
                 // enqueue all constraints that touch the changed variables
-                for &v in &[var1, var2] {
-                    for (&id, (v1, v2, _)) in &self.constraints {
-                        if id != c && !in_queue.contains(&id) && (*v1 == v || *v2 == v) {
+                for &v in &vars {
+                    for (&id, other) in &self.constraints {
+                        if id != c && !in_queue.contains(&id) && other.vars.contains(&v) {
                             prop_q.push_back(id);
                             in_queue.insert(id);
                         }
@@ -124,28 +432,62 @@ impl Engine {
         Ok(())
     }
 
-    fn revise(&mut self, var1: usize, var2: usize, kind: ConstraintKind, id: usize) -> Result<bool, PropagationError> {
-        let mut changed = false;
+    /// Fails with [`PropagationError::Overflow`] once `revisions` exceeds
+    /// `max_revisions`, reporting `c` (the constraint about to be revised) plus
+    /// everything still behind it in the queue.
+    fn check_revision_budget(&self, revisions: usize, c: usize, prop_q: &VecDeque<usize>) -> Result<(), PropagationError> {
+        if revisions > self.max_revisions {
+            let mut pending = vec![c];
+            pending.extend(prop_q.iter().copied());
+            return Err(PropagationError::Overflow(pending));
+        }
+        Ok(())
+    }
+
+    /// Revises `var1`'s domain against `var2`'s active domain under `kind`. `forward`
+    /// is `true` when `var1`/`var2` are in the constraint's original (var1, var2)
+    /// order and `false` when they have been swapped to revise the other side —
+    /// directional kinds like `LessEqual`/`LessThan`/`Offset` read it to pick the
+    /// correct relation, since e.g. `var1 <= var2` is not symmetric.
+    fn revise(&mut self, var1: usize, var2: usize, kind: ConstraintKind, id: usize, forward: bool) -> Result<Option<DomainChange>, PropagationError> {
+        let mut removed = false;
+        let mut restored = false;
 
         let active_b: Vec<i32> = self.values[var2].iter().filter(|s| s.suppressed_by.is_none()).map(|s| s.value).collect();
+        let bound_b = if forward { active_b.iter().copied().max() } else { active_b.iter().copied().min() };
+        // Suppressed values of var2 that, had they stayed active, would have
+        // supported a value of var1 — the candidate causes for a new suppression.
+        let suppressed_b: Vec<i32> = self.values[var2].iter().filter(|s| s.suppressed_by.is_some()).map(|s| s.value).collect();
 
         let domain_a = self.values.get_mut(var1).unwrap();
-        for state_a in domain_a.iter_mut() {
+        for (idx, state_a) in domain_a.iter_mut().enumerate() {
             let has_support = match kind {
                 ConstraintKind::Equality => active_b.contains(&state_a.value),
                 ConstraintKind::Inequality => active_b.iter().any(|&v_b| v_b != state_a.value),
+                ConstraintKind::LessEqual => bound_b.is_some_and(|b| if forward { state_a.value <= b } else { state_a.value >= b }),
+                ConstraintKind::LessThan => bound_b.is_some_and(|b| if forward { state_a.value < b } else { state_a.value > b }),
+                ConstraintKind::Offset(k) => {
+                    let other = if forward { state_a.value - k } else { state_a.value + k };
+                    active_b.contains(&other)
+                }
             };
 
             if has_support {
                 // This constraint no longer kills the value → possible revival
                 if state_a.suppressed_by == Some(id) {
+                    self.journal.push(JournalEntry::ValueChange { var: var1, idx, old_suppressed_by: Some(id), old_reason: state_a.reason.clone() });
                     state_a.suppressed_by = None;
-                    changed = true;
+                    state_a.reason = None;
+                    restored = true;
                 }
             } else if state_a.suppressed_by.is_none() {
-                // This constraint now kills the value
+                // This constraint now kills the value. Explain it by the values of
+                // var2 whose own suppression removed the last support for it.
+                let causes = suppressed_b.iter().copied().filter(|&w| Self::supports(kind, forward, state_a.value, w)).map(|w| (var2, w)).collect();
+                self.journal.push(JournalEntry::ValueChange { var: var1, idx, old_suppressed_by: None, old_reason: None });
                 state_a.suppressed_by = Some(id);
-                changed = true;
+                state_a.reason = Some(Reason { constraint: id, causes });
+                removed = true;
             }
         }
 
@@ -153,19 +495,141 @@ impl Engine {
             return Err(PropagationError::DomainWipeout(var1));
         }
 
-        Ok(changed)
+        // A value lost and another revived is still net "removed" for listeners:
+        // the domain didn't simply grow back, so treat it as the noteworthy event.
+        Ok(if removed {
+            Some(DomainChange::Removed)
+        } else if restored {
+            Some(DomainChange::Restored)
+        } else {
+            None
+        })
+    }
+
+    /// Whether a single value `w` of the opposite variable alone would satisfy
+    /// `kind` for value `v`, used to explain *why* a value lost support rather
+    /// than just noting that it did.
+    fn supports(kind: ConstraintKind, forward: bool, v: i32, w: i32) -> bool {
+        match kind {
+            ConstraintKind::Equality => v == w,
+            ConstraintKind::Inequality => v != w,
+            ConstraintKind::LessEqual => if forward { v <= w } else { v >= w },
+            ConstraintKind::LessThan => if forward { v < w } else { v > w },
+            ConstraintKind::Offset(k) => if forward { v - k == w } else { v + k == w },
+        }
+    }
+
+    /// Enforces generalized arc consistency for an n-ary `pred` constraint over
+    /// `vars`: each value of each variable keeps support iff some assignment of
+    /// the other variables' active domains satisfies `pred`. Returns the
+    /// `(var, DomainChange)` pairs for every variable that actually changed.
+    /// Bails out with [`PropagationError::Overflow`] instead of exhaustively
+    /// searching once the combined active-domain size is intractable.
+    fn revise_predicate(&mut self, vars: &[usize], pred: &dyn Fn(&[i32]) -> bool, id: usize) -> Result<Vec<(usize, DomainChange)>, PropagationError> {
+        let product = vars.iter().try_fold(1usize, |acc, &v| acc.checked_mul(self.val(v).len())).unwrap_or(usize::MAX);
+        if product > MAX_PREDICATE_DOMAIN_PRODUCT {
+            return Err(PropagationError::Overflow(vec![id]));
+        }
+
+        let mut changes = Vec::new();
+        for (pos, &var) in vars.iter().enumerate() {
+            // Recomputed on every position so a value revised earlier in this
+            // same call (for an earlier variable) is already reflected here.
+            let others: Vec<(usize, Vec<i32>)> = vars.iter().enumerate().filter(|&(p, _)| p != pos).map(|(p, &v)| (p, self.val(v))).collect();
+
+            let mut removed = false;
+            let mut restored = false;
+            let mut assignment = vec![0; vars.len()];
+
+            let domain = self.values.get_mut(var).unwrap();
+            for (idx, state) in domain.iter_mut().enumerate() {
+                assignment[pos] = state.value;
+                let has_support = Self::exists_support(&others, 0, &mut assignment, pred);
+
+                if has_support {
+                    if state.suppressed_by == Some(id) {
+                        self.journal.push(JournalEntry::ValueChange { var, idx, old_suppressed_by: Some(id), old_reason: state.reason.clone() });
+                        state.suppressed_by = None;
+                        state.reason = None;
+                        restored = true;
+                    }
+                } else if state.suppressed_by.is_none() {
+                    // Unlike binary `revise`, we don't track which other values'
+                    // removal caused this one's: GAC support search already
+                    // doesn't correspond to a single (var, value) pair, so the
+                    // explanation just names this constraint.
+                    self.journal.push(JournalEntry::ValueChange { var, idx, old_suppressed_by: None, old_reason: None });
+                    state.suppressed_by = Some(id);
+                    state.reason = Some(Reason { constraint: id, causes: Vec::new() });
+                    removed = true;
+                }
+            }
+
+            if !domain.iter().any(|s| s.suppressed_by.is_none()) {
+                return Err(PropagationError::DomainWipeout(var));
+            }
+
+            if removed {
+                changes.push((var, DomainChange::Removed));
+            } else if restored {
+                changes.push((var, DomainChange::Restored));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Tries every combination of `others` (each a remaining variable's
+    /// position in `assignment` paired with its active domain) until `pred`
+    /// accepts the completed `assignment`, or every combination is exhausted.
+    fn exists_support(others: &[(usize, Vec<i32>)], next: usize, assignment: &mut [i32], pred: &dyn Fn(&[i32]) -> bool) -> bool {
+        let Some((pos, domain)) = others.get(next) else {
+            return pred(assignment);
+        };
+        domain.iter().any(|&value| {
+            assignment[*pos] = value;
+            Self::exists_support(others, next + 1, assignment, pred)
+        })
     }
 
+    /// Walks the implication DAG backward from every suppressed value of `var_id`,
+    /// collecting a nogood: the set of original constraint ids whose simultaneous
+    /// presence forced the wipeout. This explains the whole chain of deletions
+    /// that led to the conflict, not just the constraints that directly suppress
+    /// `var_id`'s values.
     fn get_conflict_explanation(&self, var_id: usize) -> Vec<usize> {
-        self.values[var_id].iter().filter_map(|state| state.suppressed_by).collect::<HashSet<_>>().into_iter().collect()
+        let mut visited: HashSet<(usize, i32)> = HashSet::new();
+        let mut nogood: HashSet<usize> = HashSet::new();
+        let mut stack: Vec<(usize, i32)> = self.values[var_id].iter().map(|s| (var_id, s.value)).collect();
+
+        while let Some((var, value)) = stack.pop() {
+            if !visited.insert((var, value)) {
+                continue;
+            }
+            let Some(state) = self.values[var].iter().find(|s| s.value == value) else { continue };
+            if let Some(reason) = &state.reason {
+                nogood.insert(reason.constraint);
+                stack.extend(reason.causes.iter().copied());
+            }
+        }
+
+        nogood.into_iter().collect()
     }
 
     pub fn set_listener<F>(&mut self, var: usize, callback: F)
     where
-        F: Fn(&Engine, usize) + 'static,
+        F: Fn(&Engine, usize, usize, DomainChange) + 'static,
     {
         self.listeners.entry(var).or_default().push(Box::new(callback));
     }
+
+    /// Fires every listener registered on `var` exactly once for this change.
+    fn notify(&self, var: usize, constraint: usize, change: DomainChange) {
+        if let Some(callbacks) = self.listeners.get(&var) {
+            for callback in callbacks {
+                callback(self, var, constraint, change);
+            }
+        }
+    }
 }
 
 impl Display for Engine {
@@ -174,12 +638,23 @@ impl Display for Engine {
             let var_values: Vec<String> = var_values.iter().filter(|v| v.suppressed_by.is_none()).map(|v| v.value.to_string()).collect();
             writeln!(f, "e{}: {{{}}}", i, var_values.join(", "))?;
         }
-        for (_, (var1, var2, kind)) in &self.constraints {
-            let kind_str = match kind {
-                ConstraintKind::Equality => "==",
-                ConstraintKind::Inequality => "!=",
-            };
-            writeln!(f, "e{} {} e{}", var1, kind_str, var2)?;
+        for constraint in self.constraints.values() {
+            match &constraint.relation {
+                Relation::Binary(kind) => {
+                    let (var1, var2) = (constraint.vars[0], constraint.vars[1]);
+                    match kind {
+                        ConstraintKind::Equality => writeln!(f, "e{} == e{}", var1, var2)?,
+                        ConstraintKind::Inequality => writeln!(f, "e{} != e{}", var1, var2)?,
+                        ConstraintKind::LessEqual => writeln!(f, "e{} <= e{}", var1, var2)?,
+                        ConstraintKind::LessThan => writeln!(f, "e{} < e{}", var1, var2)?,
+                        ConstraintKind::Offset(k) => writeln!(f, "e{} == e{} + {}", var1, var2, k)?,
+                    };
+                }
+                Relation::Predicate(_) => {
+                    let vars: Vec<String> = constraint.vars.iter().map(|v| format!("e{}", v)).collect();
+                    writeln!(f, "predicate({})", vars.join(", "))?;
+                }
+            }
         }
         Ok(())
     }
@@ -235,15 +710,46 @@ mod tests {
 
         let c_id = ac.new_eq(a, b);
         assert!(ac.val(a).is_empty() || ac.val(b).is_empty());
-        assert!(c_id.as_ref().expect_err("Expected a conflict due to no overlap between a and b").1.contains(&0), "Conflict explanation should include the failed constraint ID");
+        let (failed_id, nogood) = match c_id.as_ref().expect_err("Expected a conflict due to no overlap between a and b") {
+            EngineError::Conflict(id, nogood) => (*id, nogood),
+            EngineError::Overflow(_) => panic!("expected a conflict, not an overflow"),
+        };
+        assert!(nogood.contains(&0), "Conflict explanation should include the failed constraint ID");
         // The conflict should be explained by the failed constraint itself
 
-        ac.retract_constraint(c_id.err().unwrap().0);
+        ac.retract_constraint(failed_id).unwrap();
         // After retraction, domains should return to original state
         assert_eq!(ac.val(a), vec![1, 2]);
         assert_eq!(ac.val(b), vec![3, 4]);
     }
 
+    #[test]
+    fn test_retracting_non_last_constraint_does_not_reuse_live_id() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2]);
+        let b = ac.add_var(vec![1, 2]);
+        let c = ac.add_var(vec![1, 2]);
+        let d = ac.add_var(vec![1, 2]);
+        let g = ac.add_var(vec![1, 2]);
+
+        let id0 = ac.new_eq(a, b).unwrap();
+        let id1 = ac.new_eq(a, c).unwrap();
+        let id2 = ac.new_eq(a, d).unwrap();
+        assert_eq!((id0, id1, id2), (0, 1, 2));
+
+        ac.retract_constraint(id1).unwrap(); // frees id1, leaves {id0, id2} live
+
+        // A length-based allocator would assign this the now-live id2, silently
+        // overwriting (and disabling) the still-active `a == d` constraint.
+        let id3 = ac.new_eq(a, g).unwrap();
+        assert_ne!(id3, id2, "new constraint must not collide with a still-active id");
+
+        // `a == d` (id2) must still be enforced: narrowing `d` must still
+        // propagate back to `a`.
+        ac.assign(d, 2).unwrap();
+        assert_eq!(ac.val(a), vec![2], "id2 must still be live after an out-of-order retraction");
+    }
+
     #[test]
     fn test_multiple_suppression_logic() {
         let mut ac = Engine::new();
@@ -259,13 +765,13 @@ mod tests {
         assert_eq!(ac.val(a), vec![2, 3]);
 
         // Retract first inequality
-        ac.retract_constraint(id0.unwrap());
+        ac.retract_constraint(id0.unwrap()).unwrap();
 
         // CRITICAL: Value '1' in 'a' was suppressed by id0.
         // Even after retracting id0, '1' should stay suppressed because id1 (a != c) still forbids it.
         assert_eq!(ac.val(a), vec![2, 3], "Value 1 should still be suppressed by the other inequality");
 
-        ac.retract_constraint(id1.unwrap());
+        ac.retract_constraint(id1.unwrap()).unwrap();
         assert_eq!(ac.val(a), vec![1, 2, 3], "All values should be restored now");
     }
 
@@ -301,4 +807,305 @@ mod tests {
         assert_eq!(ac.val(b), vec![2]);
         assert_eq!(ac.val(c), vec![3]);
     }
+
+    #[test]
+    fn test_checkpoint_restore_after_propagation() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2, 3]);
+        let b = ac.add_var(vec![2, 3, 4]);
+
+        let checkpoint = ac.push();
+        let _ = ac.new_eq(a, b); // a,b: {2, 3}
+        assert_eq!(ac.val(a), vec![2, 3]);
+
+        ac.pop_to(checkpoint);
+        // The constraint and every value it suppressed must both be undone.
+        assert_eq!(ac.val(a), vec![1, 2, 3]);
+        assert_eq!(ac.val(b), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_assign_try_and_backtrack() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2, 3]);
+        let b = ac.add_var(vec![1, 2, 3]);
+        let _ = ac.new_eq(a, b);
+
+        let checkpoint = ac.push();
+        assert!(ac.assign(a, 2).is_ok());
+        assert_eq!(ac.val(a), vec![2]);
+        assert_eq!(ac.val(b), vec![2]); // propagated through the equality
+
+        ac.pop_to(checkpoint);
+        assert_eq!(ac.val(a), vec![1, 2, 3]);
+        assert_eq!(ac.val(b), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_assign_conflict_is_undoable() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2]);
+        let b = ac.add_var(vec![1]);
+        let _ = ac.new_eq(a, b); // a: {1}
+
+        let checkpoint = ac.push();
+        assert!(ac.assign(a, 2).is_err());
+
+        ac.pop_to(checkpoint);
+        assert_eq!(ac.val(a), vec![1]);
+    }
+
+    #[test]
+    fn test_less_equal_bounds_pruning() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2, 5]);
+        let b = ac.add_var(vec![0, 2, 3]);
+
+        let _ = ac.new_le(a, b); // a <= b
+
+        // b's max active value is 3, so a's 5 has no support.
+        assert_eq!(ac.val(a), vec![1, 2]);
+        // a's min active value is 1, so b's 0 has no support.
+        assert_eq!(ac.val(b), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_less_than_strict_pruning() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 3]);
+        let b = ac.add_var(vec![2, 3]);
+
+        let _ = ac.new_lt(a, b); // a < b
+
+        // 3 in a has no support (b's max is 3, and 3 < 3 is false).
+        assert_eq!(ac.val(a), vec![1]);
+        // a's min active value is 1, and both 2 and 3 in b already satisfy > 1.
+        assert_eq!(ac.val(b), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_offset_constraint_pruning() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2, 3, 4]);
+        let b = ac.add_var(vec![1, 2]);
+
+        let _ = ac.new_offset(a, b, 2); // a == b + 2
+
+        assert_eq!(ac.val(a), vec![3, 4]);
+        assert_eq!(ac.val(b), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_offset_retraction_revives_values() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2, 3, 4]);
+        let b = ac.add_var(vec![1, 2]);
+
+        let id = ac.new_offset(a, b, 2).unwrap();
+        assert_eq!(ac.val(a), vec![3, 4]);
+
+        ac.retract_constraint(id).unwrap();
+        assert_eq!(ac.val(a), vec![1, 2, 3, 4]);
+        assert_eq!(ac.val(b), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_transitive_conflict_explanation() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1]);
+        let b = ac.add_var(vec![1, 2]);
+        let c = ac.add_var(vec![1, 2]);
+        let d = ac.add_var(vec![2, 3]);
+
+        let _ = ac.new_eq(a, b); // id0: a is {1}, forces b to {1}
+        let _ = ac.new_eq(b, c); // id1: b is {1}, forces c to {1}
+        let err = ac.new_eq(c, d).unwrap_err(); // id2: c is {1}, d has no 1 -> wipeout
+
+        // The minimal conflict set must include id0: without the a==b constraint
+        // pinning b (and transitively c) to 1, c would still hold 2 and survive
+        // against d. A flat "current suppressors of c" view would miss it.
+        let EngineError::Conflict(_, mut nogood) = err else { panic!("expected a conflict, not an overflow") };
+        nogood.sort();
+        assert_eq!(nogood, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_cached_nogood_skips_repropagation() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2]);
+        let b = ac.add_var(vec![3, 4]);
+        let c = ac.add_var(vec![5, 6]);
+
+        let err = ac.new_eq(a, b).unwrap_err(); // id0 alone is already a nogood
+        assert_eq!(err, EngineError::Conflict(0, vec![0]));
+
+        // id0 is still active (never retracted); any further constraint must
+        // fail immediately off the cached nogood rather than re-propagating.
+        let err2 = ac.new_eq(a, c).unwrap_err();
+        assert_eq!(err2, EngineError::Conflict(1, vec![0]));
+
+        // The short-circuited constraint must never have been registered: it
+        // was rejected before propagating, so c's domain is untouched and
+        // retracting id0 must not later "discover" a never-enforced id1.
+        assert_eq!(ac.val(c), vec![5, 6]);
+        ac.retract_constraint(0).unwrap();
+        assert_eq!(ac.val(a), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_retraction_invalidates_stale_nogood() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2]);
+        let b = ac.add_var(vec![3, 4]);
+
+        ac.new_eq(a, b).unwrap_err(); // id0 fails and is recorded as a nogood
+        ac.retract_constraint(0).unwrap(); // domains fully restored, id0 gone for good
+
+        // id0 is never reused (ids are never reused), but the stale nogood it
+        // left behind must not spuriously reject this new, unrelated, trivially
+        // satisfiable constraint either.
+        let id = ac.new_le(a, b).unwrap();
+        assert_eq!(id, 1);
+    }
+
+    #[test]
+    fn test_revision_budget_overflow() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2, 3]);
+        let b = ac.add_var(vec![2, 3, 4]);
+        ac.set_max_revisions(1); // one revise call is allowed, a second is not
+
+        let err = ac.new_eq(a, b).unwrap_err();
+        assert_eq!(err, EngineError::Overflow(vec![0]));
+    }
+
+    #[test]
+    fn test_revision_budget_overflow_reports_pending_queue() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2, 3]);
+        let b = ac.add_var(vec![2, 3, 4]);
+        let c = ac.add_var(vec![3, 4, 5]);
+
+        // Neither constraint narrows anything yet, so both are free to set up
+        // under the default unlimited budget.
+        let c1 = ac.new_le(a, b).unwrap();
+        let c2 = ac.new_le(a, c).unwrap();
+
+        ac.set_max_revisions(0); // no revise calls are allowed at all
+        let err = ac.assign(a, 1).unwrap_err();
+        let EngineError::Overflow(mut pending) = err else { panic!("expected an overflow, not a conflict") };
+        pending.sort();
+        let mut expected = vec![c1, c2];
+        expected.sort();
+        assert_eq!(pending, expected, "both constraints queued by `assign` should be reported as still pending");
+    }
+
+    #[test]
+    fn test_listener_fires_on_suppression_and_revival() {
+        use std::rc::Rc;
+        use std::cell::RefCell;
+
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2, 3]);
+        let b = ac.add_var(vec![2, 3, 4]);
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let events_clone = Rc::clone(&events);
+        ac.set_listener(a, move |_engine, var, constraint, change| {
+            events_clone.borrow_mut().push((var, constraint, change));
+        });
+
+        let id = ac.new_eq(a, b).unwrap(); // a: {2, 3} — fires once for var a
+        assert_eq!(*events.borrow(), vec![(a, id, DomainChange::Removed)]);
+
+        ac.retract_constraint(id).unwrap(); // 1 revives in a — fires once more
+        assert_eq!(events.borrow().len(), 2);
+        assert_eq!(events.borrow()[1], (a, id, DomainChange::Restored));
+    }
+
+    #[test]
+    fn test_listener_fires_once_per_pass_with_multiple_narrowing_constraints() {
+        use std::rc::Rc;
+        use std::cell::RefCell;
+
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2, 3, 4, 5]);
+        let b = ac.add_var(vec![1, 2, 3, 4, 5]);
+        let c = ac.add_var(vec![1, 2, 3, 4, 5]);
+        let y = ac.add_var(vec![1]);
+
+        let yb = ac.new_eq(y, b).unwrap(); // b: {1}
+        let _ = ac.new_eq(y, c).unwrap(); // c: {1}
+        let _ = ac.new_le(a, b).unwrap(); // a <= b narrows a to {1}
+        let _ = ac.new_le(a, c).unwrap(); // a already {1}, no-op
+
+        let fire_count = Rc::new(RefCell::new(0));
+        let fire_count_clone = Rc::clone(&fire_count);
+        ac.set_listener(a, move |_engine, _var, _constraint, _change| {
+            *fire_count_clone.borrow_mut() += 1;
+        });
+
+        // Retracting y == b revives b to {1..5}, which re-propagates in one pass:
+        // `a <= b` first restores a's upper values, then `a <= c` (c is still
+        // pinned to {1}) immediately re-suppresses them. Two change events on
+        // `a`, but the listener must still fire only once for the pass.
+        ac.retract_constraint(yb).unwrap();
+        assert_eq!(ac.val(a), vec![1]);
+
+        assert_eq!(*fire_count.borrow(), 1, "one fire per pass even though `a` changed twice");
+    }
+
+    #[test]
+    fn test_predicate_all_different_over_three_vars() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2]);
+        let b = ac.add_var(vec![1, 2]);
+        let c = ac.add_var(vec![1, 2, 3]);
+
+        let _ = ac
+            .new_predicate(vec![a, b, c], Box::new(|vals| vals[0] != vals[1] && vals[1] != vals[2] && vals[0] != vals[2]))
+            .unwrap();
+
+        // a and b exhaust {1, 2} between them, so c can never match either and
+        // is pinned to 3.
+        assert_eq!(ac.val(c), vec![3]);
+    }
+
+    #[test]
+    fn test_predicate_binary_matches_equivalent_binary_constraint() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2, 3]);
+        let b = ac.add_var(vec![2, 3, 4]);
+
+        let _ = ac.new_predicate(vec![a, b], Box::new(|vals| vals[0] == vals[1])).unwrap();
+
+        assert_eq!(ac.val(a), vec![2, 3]);
+        assert_eq!(ac.val(b), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_predicate_retraction_revives_suppressed_values() {
+        let mut ac = Engine::new();
+        let a = ac.add_var(vec![1, 2, 3]);
+        let b = ac.add_var(vec![2, 3, 4]);
+
+        let id = ac.new_predicate(vec![a, b], Box::new(|vals| vals[0] == vals[1])).unwrap();
+        assert_eq!(ac.val(a), vec![2, 3]);
+
+        ac.retract_constraint(id).unwrap();
+        assert_eq!(ac.val(a), vec![1, 2, 3]);
+        assert_eq!(ac.val(b), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_predicate_overflow_when_domain_product_too_large() {
+        let mut ac = Engine::new();
+        let big: Vec<i32> = (0..1000).collect();
+        let a = ac.add_var(big.clone());
+        let b = ac.add_var(big.clone());
+        let c = ac.add_var(big);
+
+        let err = ac.new_predicate(vec![a, b, c], Box::new(|vals| vals[0] == vals[1] && vals[1] == vals[2])).unwrap_err();
+        assert_eq!(err, EngineError::Overflow(vec![0]));
+    }
 }